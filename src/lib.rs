@@ -0,0 +1,816 @@
+use anyhow::anyhow;
+use std::io::{self, BufReader, Read, Write};
+
+/// Pause (in milliseconds) written after a TZX block produced from a TAP header block.
+const HEADER_PAUSE_MS: u16 = 100;
+
+/// Pause (in milliseconds) written after a TZX block produced from a TAP data block.
+const DATA_PAUSE_MS: u16 = 1000;
+
+/// How strictly to treat a TAP block whose trailing XOR checksum byte doesn't match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChecksumPolicy {
+    /// Ignore checksum mismatches entirely.
+    Ignore,
+    /// Print a warning to stderr but keep converting.
+    #[default]
+    Warn,
+    /// Treat a checksum mismatch as a hard error.
+    Error,
+}
+
+/// Which TZX metadata blocks to emit from each TAP header block, ahead of its
+/// standard-speed data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Metadata {
+    /// Emit a Text Description block (ID 0x30) naming the program.
+    pub text_description: bool,
+    /// Emit an Archive Info block (ID 0x32) naming the program.
+    pub archive_info: bool,
+}
+
+/// How strictly to treat a TAP block that declares more bytes than the input actually
+/// has left (including a final block truncated part-way through).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Validation {
+    /// Warn (to stderr) and convert whatever of the block was actually read.
+    #[default]
+    Lenient,
+    /// Fail with a structured error naming the byte offset and the violation.
+    Strict,
+}
+
+/// Options controlling how [`convert`] turns a TAP stream into a TZX stream.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ConvertOptions {
+    /// How to handle a TAP block with a bad XOR checksum.
+    pub checksums: ChecksumPolicy,
+    /// Which metadata blocks to emit from TAP header blocks.
+    pub metadata: Metadata,
+    /// How to handle a block whose declared length outruns the input.
+    pub validation: Validation,
+}
+
+/// The role of a TAP block, determined by its first (flag) byte.
+///
+/// A Spectrum header block is always 19 bytes: flag(1) + type(1) + filename(10) +
+/// data-length(2) + param1(2) + param2(2) + XOR-checksum(1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BlockKind {
+    /// flag byte 0x00.
+    Header,
+    /// flag byte 0xFF.
+    Data,
+    /// Any other flag byte.
+    Other,
+}
+
+impl BlockKind {
+    fn from_flag(flag: u8) -> Self {
+        match flag {
+            0x00 => BlockKind::Header,
+            0xFF => BlockKind::Data,
+            _ => BlockKind::Other,
+        }
+    }
+
+    /// The TZX pause to use after a block of this kind, chosen so that a header is
+    /// never left stalling on the tape before the data block that follows it.
+    fn pause_ms(self) -> u16 {
+        match self {
+            BlockKind::Header => HEADER_PAUSE_MS,
+            BlockKind::Data | BlockKind::Other => DATA_PAUSE_MS,
+        }
+    }
+}
+
+/// Converts a TAP byte stream read from `input` into a TZX byte stream written to `output`.
+///
+/// Blocks are parsed one at a time off an internal `BufReader`, so `input` can be
+/// arbitrarily large (a file, a pipe, stdin) without ever holding more than a single
+/// TAP block in memory at once. Each TAP block's flag byte determines whether it's a
+/// header or data block, which in turn picks the pause written after the equivalent
+/// TZX block, and `options.checksums` controls how a bad XOR checksum is handled. If
+/// `options.metadata` asks for it, each header block also contributes a Text
+/// Description and/or Archive Info block ahead of its standard-speed data.
+/// `options.validation` controls what happens when a block declares more bytes than
+/// the input has left to give it - in [`Validation::Lenient`] mode (the default) as
+/// much of the block as exists is still converted.
+///
+/// Returns the number of TAP blocks read from `input`. This counts zero-length TAP
+/// blocks, which write nothing, and does not count any metadata blocks written.
+///
+/// # Errors
+///
+/// Returns an error if a block's length prefix is truncated, if `options.validation`
+/// is [`Validation::Strict`] and a block runs past the end of the input, if a block's
+/// checksum doesn't match and `options.checksums` is [`ChecksumPolicy::Error`] (or
+/// `options.validation` is [`Validation::Strict`] and `options.checksums` wasn't
+/// explicitly relaxed to [`ChecksumPolicy::Ignore`]), or if there is a problem reading
+/// from `input` or writing to `output`.
+pub fn convert<R, W>(input: R, output: &mut W, options: ConvertOptions) -> anyhow::Result<usize>
+where
+    R: Read,
+    W: Write,
+{
+    let mut tap = BufReader::new(input);
+    write_tzx_header(output)?;
+
+    // Strict validation means a bad checksum should be a hard error too, unless the
+    // caller explicitly asked to have checksums ignored.
+    let checksums = match (options.validation, options.checksums) {
+        (Validation::Strict, ChecksumPolicy::Warn) => ChecksumPolicy::Error,
+        (_, policy) => policy,
+    };
+
+    let mut pos = 0usize;
+    let mut block_count = 0usize;
+
+    while let Some(block_len) = read_le_u16(&mut tap, pos)? {
+        pos += 2;
+
+        if block_len != 0 {
+            let block = read_block(&mut tap, block_len, pos, options.validation)?;
+
+            check_checksum(&block, pos, checksums)?;
+
+            // A lenient read of a truncated block can leave it empty, with no flag byte.
+            let kind = block
+                .first()
+                .map_or(BlockKind::Other, |&flag| BlockKind::from_flag(flag));
+
+            if kind == BlockKind::Header {
+                if let Some(info) = HeaderInfo::parse(&block) {
+                    if options.metadata.text_description {
+                        write_text_description_block(&info.title(), output)?;
+                    }
+                    if options.metadata.archive_info {
+                        write_archive_info_block(&info.title(), output)?;
+                    }
+                }
+            }
+
+            write_tzx_block(&block, kind.pause_ms(), output)?;
+        }
+
+        pos += block_len as usize;
+        block_count += 1;
+    }
+
+    output.flush()?;
+
+    Ok(block_count)
+}
+
+/// Magic bytes (excluding version) that every TZX file begins with.
+const TZX_MAGIC: &[u8; 8] = b"ZXTape!\x1A";
+
+/// Reads a TZX byte stream from `input` and reconstructs the TAP byte stream it was
+/// made from, writing it to `output`.
+///
+/// Standard Speed Data (0x10) and Turbo Speed Data (0x11) blocks become ordinary TAP
+/// blocks (a turbo block's data is copied in as-is, losing only its timing). Text
+/// Description (0x30), Archive Info (0x32) and Pause (0x20) blocks carry no tape data
+/// of their own and are skipped over by reading past their documented length.
+///
+/// Returns the number of TAP blocks written to `output`.
+///
+/// # Errors
+///
+/// Returns an error if `input` doesn't start with the TZX magic bytes, if it contains
+/// a block ID this function doesn't know how to convert or skip, or if there is a
+/// problem reading from `input` or writing to `output`.
+pub fn tzx_to_tap<R, W>(input: R, output: &mut W) -> anyhow::Result<usize>
+where
+    R: Read,
+    W: Write,
+{
+    let mut tzx = BufReader::new(input);
+
+    let mut magic = [0u8; 8];
+    tzx.read_exact(&mut magic)?;
+    if &magic != TZX_MAGIC {
+        return Err(anyhow!("not a TZX file: missing 'ZXTape!' magic"));
+    }
+    skip(&mut tzx, 2)?; // major, minor version
+
+    let mut block_count = 0usize;
+    let mut id = [0u8; 1];
+
+    while tzx.read(&mut id)? != 0 {
+        match id[0] {
+            0x10 => {
+                skip(&mut tzx, 2)?; // pause (ms)
+                let len = read_len(&mut tzx, 2)?;
+                copy_tap_block(&mut tzx, len, output)?;
+                block_count += 1;
+            }
+            0x11 => {
+                skip(&mut tzx, 15)?; // pilot/sync/bit timings, pilot length, used bits, pause
+                let len = read_len(&mut tzx, 3)?;
+                copy_tap_block(&mut tzx, len, output)?;
+                block_count += 1;
+            }
+            0x20 => skip(&mut tzx, 2)?, // pause length; no payload
+            0x30 => {
+                let len = read_len(&mut tzx, 1)?;
+                skip(&mut tzx, len)?;
+            }
+            0x32 => {
+                let len = read_len(&mut tzx, 2)?;
+                skip(&mut tzx, len)?;
+            }
+            other => return Err(anyhow!("unsupported TZX block ID {:#04x}", other)),
+        }
+    }
+
+    output.flush()?;
+
+    Ok(block_count)
+}
+
+/// Reads a little-endian length field `width` bytes wide (1, 2 or 3 bytes) from `r`.
+fn read_len<R>(r: &mut R, width: usize) -> anyhow::Result<usize>
+where
+    R: Read,
+{
+    let mut buf = [0u8; 4];
+    r.read_exact(&mut buf[..width])?;
+    Ok(u32::from_le_bytes(buf) as usize)
+}
+
+/// Discards exactly `len` bytes from `r`.
+fn skip<R>(r: &mut R, len: usize) -> io::Result<()>
+where
+    R: Read,
+{
+    let skipped = io::copy(&mut r.take(len as u64), &mut io::sink())?;
+    if skipped < len as u64 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "input ended before the bytes to be skipped",
+        ));
+    }
+    Ok(())
+}
+
+/// Reads `len` bytes of TZX block data from `r` and writes them to `out` as a TAP
+/// block: a little-endian u16 length prefix followed by the data itself.
+///
+/// # Errors
+///
+/// Returns an error if `len` doesn't fit in the u16 length prefix a TAP block uses -
+/// this can happen for a Turbo Speed Data block, whose 3-byte length field can address
+/// more data than TAP's format allows for a single block.
+fn copy_tap_block<R, W>(r: &mut R, len: usize, out: &mut W) -> anyhow::Result<()>
+where
+    R: Read,
+    W: Write,
+{
+    let tap_len: u16 = len.try_into().map_err(|_| {
+        anyhow!(
+            "TZX block holds {} byte(s) of data, too large for a TAP block (max {})",
+            len,
+            u16::MAX
+        )
+    })?;
+
+    let mut data = vec![0u8; len];
+    r.read_exact(&mut data)?;
+    out.write_all(&tap_len.to_le_bytes())?;
+    out.write_all(&data)?;
+    Ok(())
+}
+
+/// A specific way a TAP block can fail validation, naming the byte offset of the
+/// block it was found in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Violation {
+    /// A block declared more bytes than the input had left.
+    Truncated {
+        /// Offset of the block in the input.
+        pos: usize,
+        /// Length the block's header declared.
+        declared: u16,
+        /// Bytes actually available.
+        actual: usize,
+    },
+    /// A block's trailing XOR checksum byte didn't match the XOR of its payload.
+    BadChecksum {
+        /// Offset of the block in the input.
+        pos: usize,
+        /// Checksum byte the payload XORs to.
+        expected: u8,
+        /// Checksum byte actually found.
+        found: u8,
+    },
+}
+
+impl std::fmt::Display for Violation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Violation::Truncated {
+                pos,
+                declared,
+                actual,
+            } => write!(
+                f,
+                "block at {} declares {} byte(s) but only {} were available",
+                pos, declared, actual
+            ),
+            Violation::BadChecksum {
+                pos,
+                expected,
+                found,
+            } => write!(
+                f,
+                "checksum mismatch at {}: expected {:#04x}, found {:#04x}",
+                pos, expected, found
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Violation {}
+
+/// Reads a TAP block's `declared_len` bytes of payload from `r`.
+///
+/// If the input runs out before `declared_len` bytes are available, the returned
+/// `Vec` is shorter than `declared_len` in [`Validation::Lenient`] mode (with a
+/// warning printed to stderr); in [`Validation::Strict`] mode this is an error.
+/// `pos` is the offset of the block in the input, used only for diagnostics.
+fn read_block<R>(
+    r: &mut R,
+    declared_len: u16,
+    pos: usize,
+    validation: Validation,
+) -> anyhow::Result<Vec<u8>>
+where
+    R: Read,
+{
+    let mut block = vec![0u8; declared_len as usize];
+    let mut read = 0;
+
+    while read < block.len() {
+        match r.read(&mut block[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+
+    if read < block.len() {
+        let violation = Violation::Truncated {
+            pos,
+            declared: declared_len,
+            actual: read,
+        };
+
+        match validation {
+            Validation::Strict => return Err(violation.into()),
+            Validation::Lenient => {
+                eprintln!("warning: {}", violation);
+                block.truncate(read);
+            }
+        }
+    }
+
+    Ok(block)
+}
+
+/// Validates `block`'s trailing XOR checksum byte against the XOR of the preceding
+/// bytes, acting according to `policy`. `pos` is the offset of `block` in the input,
+/// used only for diagnostics.
+fn check_checksum(block: &[u8], pos: usize, policy: ChecksumPolicy) -> anyhow::Result<()> {
+    if policy == ChecksumPolicy::Ignore {
+        return Ok(());
+    }
+
+    if let Some((checksum, rest)) = block.split_last() {
+        let expected = rest.iter().fold(0u8, |acc, b| acc ^ b);
+
+        if expected != *checksum {
+            let violation = Violation::BadChecksum {
+                pos,
+                expected,
+                found: *checksum,
+            };
+
+            match policy {
+                ChecksumPolicy::Error => return Err(violation.into()),
+                ChecksumPolicy::Warn => eprintln!("warning: {}", violation),
+                ChecksumPolicy::Ignore => unreachable!(),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The Spectrum program type recorded in a TAP header's type byte.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProgramType {
+    Program,
+    NumberArray,
+    CharacterArray,
+    Bytes,
+    Unknown(u8),
+}
+
+impl ProgramType {
+    fn from_byte(b: u8) -> Self {
+        match b {
+            0 => ProgramType::Program,
+            1 => ProgramType::NumberArray,
+            2 => ProgramType::CharacterArray,
+            3 => ProgramType::Bytes,
+            other => ProgramType::Unknown(other),
+        }
+    }
+
+    fn description(self) -> &'static str {
+        match self {
+            ProgramType::Program => "Program",
+            ProgramType::NumberArray => "Number array",
+            ProgramType::CharacterArray => "Character array",
+            ProgramType::Bytes => "Bytes",
+            ProgramType::Unknown(_) => "Unknown",
+        }
+    }
+}
+
+/// The program name and type extracted from a 19-byte TAP header block.
+struct HeaderInfo {
+    name: String,
+    program_type: ProgramType,
+}
+
+impl HeaderInfo {
+    /// Parses `block` as a TAP header, returning `None` unless it's a 19-byte header
+    /// block (flag byte 0x00).
+    fn parse(block: &[u8]) -> Option<HeaderInfo> {
+        if block.len() != 19 || BlockKind::from_flag(block[0]) != BlockKind::Header {
+            return None;
+        }
+
+        Some(HeaderInfo {
+            name: String::from_utf8_lossy(&block[2..12])
+                .trim_end()
+                .to_string(),
+            program_type: ProgramType::from_byte(block[1]),
+        })
+    }
+
+    /// The human-readable title used in TZX metadata blocks, e.g. `"Jet Set Willy (Bytes)"`.
+    fn title(&self) -> String {
+        format!("{} ({})", self.name, self.program_type.description())
+    }
+}
+
+/// Writes a TZX Text Description block (ID 0x30) containing `text`.
+fn write_text_description_block<W>(text: &str, out: &mut W) -> io::Result<()>
+where
+    W: Write,
+{
+    let bytes = &text.as_bytes()[..text.len().min(u8::MAX as usize)];
+    out.write_all(&[0x30, bytes.len() as u8])?;
+    out.write_all(bytes)
+}
+
+/// Writes a TZX Archive Info block (ID 0x32) containing a single full-title (text ID
+/// 0x00) text record holding `text`.
+fn write_archive_info_block<W>(text: &str, out: &mut W) -> io::Result<()>
+where
+    W: Write,
+{
+    let bytes = &text.as_bytes()[..text.len().min(u8::MAX as usize)];
+    let total_len: u16 = 1 + 1 + 1 + bytes.len() as u16; // count + text-id + length + text
+
+    out.write_all(&[0x32])?;
+    out.write_all(&total_len.to_le_bytes())?;
+    out.write_all(&[0x01])?; // one text record
+    out.write_all(&[0x00, bytes.len() as u8])?; // text-id 0x00: full title
+    out.write_all(bytes)
+}
+
+/// Write the tzx file header magic bytes
+fn write_tzx_header<W>(out: &mut W) -> io::Result<()>
+where
+    W: Write,
+{
+    // Magic start bytes plus version
+    out.write_all(&[b'Z', b'X', b'T', b'a', b'p', b'e', b'!', 0x1A, 1, 20])
+}
+
+/// Writes a full TZX standard speed data block containing `data` to the output, pausing
+/// for `pause_ms` milliseconds afterwards.
+fn write_tzx_block<W>(data: &[u8], pause_ms: u16, out: &mut W) -> io::Result<()>
+where
+    W: Write,
+{
+    // Write the TZX block header
+
+    //  0
+    //  0 1 2 3 4
+    // +-+-+-+-+-+
+    // |I| P.| L.|
+    // +-+-+-+-+-+
+    //
+    // I - Block ID. 0x10 for Standard speed data block
+    // P - Pause after this block (ms.) (little endian)
+    // L - Length of data that follow (little endian)
+    //
+    out.write_all(&[0x10])?; // I
+    out.write_all(&pause_ms.to_le_bytes())?; // P
+    out.write_all(&(data.len() as u16).to_le_bytes())?; // L
+
+    // Write the TZX block data
+    out.write_all(data)
+}
+
+/// Attempts to read a little-endian u16 length prefix from `r`.
+///
+/// Returns `Ok(None)` at a clean end of stream (no bytes read at all), or an error
+/// if the stream ends partway through the prefix. `pos` is the offset of the prefix
+/// in the input, used only for diagnostics.
+fn read_le_u16<R>(r: &mut R, pos: usize) -> anyhow::Result<Option<u16>>
+where
+    R: Read,
+{
+    let mut buf = [0u8; 2];
+    let mut read = 0;
+
+    while read < buf.len() {
+        match r.read(&mut buf[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+
+    match read {
+        0 => Ok(None),
+        2 => Ok(Some(u16::from_le_bytes(buf))),
+        n => Err(anyhow!(
+            "Expected u16 length prefix but found only {} byte(s) - malformed input at {}?",
+            n,
+            pos
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+
+    use crate::*;
+    use std::io::Cursor;
+
+    fn opts(checksums: ChecksumPolicy) -> ConvertOptions {
+        ConvertOptions {
+            checksums,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn single_header_block() {
+        let tap = [
+            0x13, 0x00, 0x00, 0x00, 0x4D, 0x61, 0x6E, 0x69, 0x63, 0x4D, 0x69, 0x6E, 0x65, 0x72,
+            0x45, 0x00, 0x0A, 0x00, 0x45, 0x00, 0x1F,
+        ];
+        let mut out = Vec::with_capacity(10 + 3 + tap.len()); // file header + block header + data
+        let block_count = convert(Cursor::new(tap), &mut out, opts(ChecksumPolicy::Error)).unwrap();
+
+        assert_eq!(1, block_count, "We expect to have created a single block");
+
+        // We expect to have the TZX file header, plus a single TZX block with header and data
+        let file_header = [b'Z', b'X', b'T', b'a', b'p', b'e', b'!', 0x1A, 1, 20];
+        let block_header = [0x10, 0x64, 0x00]; // Standard speed data block with a pause of 100ms
+        let expected: Vec<u8> = file_header
+            .iter()
+            .chain(block_header.iter())
+            .chain(tap.iter())
+            .copied()
+            .collect();
+
+        assert_eq!(expected, out, "unexpected tzx byte stream");
+    }
+
+    #[test]
+    fn data_block_gets_default_pause() {
+        let data = [0xFFu8, 0x01, 0x02, 0xFC]; // flag 0xFF, payload, correct XOR checksum
+        let mut tap = Vec::new();
+        tap.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        tap.extend_from_slice(&data);
+
+        let mut out = Vec::new();
+        convert(Cursor::new(tap), &mut out, opts(ChecksumPolicy::Error)).unwrap();
+
+        assert_eq!(&out[10..13], &[0x10, 0xE8, 0x03]); // 1000ms pause
+    }
+
+    #[test]
+    fn bad_checksum_is_an_error_with_error_policy() {
+        let data = [0xFFu8, 0x01, 0x02, 0x00]; // wrong checksum
+        let mut tap = Vec::new();
+        tap.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        tap.extend_from_slice(&data);
+
+        let mut out = Vec::new();
+        assert!(convert(Cursor::new(tap), &mut out, opts(ChecksumPolicy::Error)).is_err());
+    }
+
+    #[test]
+    fn bad_checksum_is_a_warning_by_default() {
+        let data = [0xFFu8, 0x01, 0x02, 0x00]; // wrong checksum
+        let mut tap = Vec::new();
+        tap.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        tap.extend_from_slice(&data);
+
+        let mut out = Vec::new();
+        let block_count = convert(Cursor::new(tap), &mut out, opts(ChecksumPolicy::Warn)).unwrap();
+
+        assert_eq!(1, block_count);
+    }
+
+    #[test]
+    fn empty_input() {
+        let mut out = Vec::new();
+        let block_count = convert(Cursor::new([]), &mut out, opts(ChecksumPolicy::Error)).unwrap();
+
+        assert_eq!(0, block_count);
+        assert_eq!(
+            [b'Z', b'X', b'T', b'a', b'p', b'e', b'!', 0x1A, 1, 20].to_vec(),
+            out
+        );
+    }
+
+    #[test]
+    fn truncated_length_prefix_is_an_error() {
+        let mut out = Vec::new();
+        assert!(convert(Cursor::new([0x01]), &mut out, opts(ChecksumPolicy::Error)).is_err());
+    }
+
+    #[test]
+    fn header_emits_requested_metadata_blocks() {
+        let tap = [
+            0x13, 0x00, 0x00, 0x00, 0x4D, 0x61, 0x6E, 0x69, 0x63, 0x4D, 0x69, 0x6E, 0x65, 0x72,
+            0x45, 0x00, 0x0A, 0x00, 0x45, 0x00, 0x1F,
+        ];
+        let options = ConvertOptions {
+            checksums: ChecksumPolicy::Error,
+            metadata: Metadata {
+                text_description: true,
+                archive_info: true,
+            },
+            ..Default::default()
+        };
+
+        let mut out = Vec::new();
+        convert(Cursor::new(tap), &mut out, options).unwrap();
+
+        let title = b"ManicMiner (Program)";
+        let mut expected = vec![0x30, title.len() as u8];
+        expected.extend_from_slice(title);
+        expected.extend_from_slice(&[0x32]);
+        expected.extend_from_slice(&(3 + title.len() as u16).to_le_bytes());
+        expected.extend_from_slice(&[0x01, 0x00, title.len() as u8]);
+        expected.extend_from_slice(title);
+
+        assert_eq!(&out[10..10 + expected.len()], expected.as_slice());
+    }
+
+    #[test]
+    fn tzx_to_tap_round_trips_standard_speed_data() {
+        let tap = [
+            0x13, 0x00, 0x00, 0x00, 0x4D, 0x61, 0x6E, 0x69, 0x63, 0x4D, 0x69, 0x6E, 0x65, 0x72,
+            0x45, 0x00, 0x0A, 0x00, 0x45, 0x00, 0x1F,
+        ];
+        let mut tzx = Vec::new();
+        convert(Cursor::new(tap), &mut tzx, opts(ChecksumPolicy::Error)).unwrap();
+
+        let mut out = Vec::new();
+        let block_count = tzx_to_tap(Cursor::new(tzx), &mut out).unwrap();
+
+        assert_eq!(1, block_count);
+        assert_eq!(tap.to_vec(), out);
+    }
+
+    #[test]
+    fn tzx_to_tap_decodes_turbo_speed_data() {
+        let data = [0xFFu8, 0x01, 0x02, 0xFC];
+        let mut tzx = Vec::new();
+        tzx.extend_from_slice(b"ZXTape!\x1A");
+        tzx.extend_from_slice(&[1, 20]); // version
+        tzx.extend_from_slice(&[0x11]); // Turbo Speed Data
+        tzx.extend_from_slice(&[0u8; 15]); // fixed timing header
+        tzx.extend_from_slice(&(data.len() as u32).to_le_bytes()[..3]); // 3-byte length
+        tzx.extend_from_slice(&data);
+
+        let mut out = Vec::new();
+        let block_count = tzx_to_tap(Cursor::new(tzx), &mut out).unwrap();
+
+        assert_eq!(1, block_count);
+        let mut expected = (data.len() as u16).to_le_bytes().to_vec();
+        expected.extend_from_slice(&data);
+        assert_eq!(expected, out);
+    }
+
+    #[test]
+    fn tzx_to_tap_rejects_turbo_data_too_large_for_a_tap_block() {
+        let mut tzx = Vec::new();
+        tzx.extend_from_slice(b"ZXTape!\x1A");
+        tzx.extend_from_slice(&[1, 20]); // version
+        tzx.extend_from_slice(&[0x11]); // Turbo Speed Data
+        tzx.extend_from_slice(&[0u8; 15]); // fixed timing header
+        tzx.extend_from_slice(&(u16::MAX as u32 + 1).to_le_bytes()[..3]); // too big for a u16 TAP length
+
+        let mut out = Vec::new();
+        assert!(tzx_to_tap(Cursor::new(tzx), &mut out).is_err());
+    }
+
+    #[test]
+    fn tzx_to_tap_skips_metadata_blocks() {
+        let mut tzx = Vec::new();
+        tzx.extend_from_slice(b"ZXTape!\x1A");
+        tzx.extend_from_slice(&[1, 20]);
+        tzx.extend_from_slice(&[0x30, 0x02, b'h', b'i']); // Text Description
+        tzx.extend_from_slice(&[0x20, 0x00, 0x00]); // Pause
+        tzx.extend_from_slice(&[0x32, 0x01, 0x00, 0x00]); // Archive Info, zero records
+
+        let mut out = Vec::new();
+        let block_count = tzx_to_tap(Cursor::new(tzx), &mut out).unwrap();
+
+        assert_eq!(0, block_count);
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn tzx_to_tap_rejects_bad_magic() {
+        let mut out = Vec::new();
+        assert!(tzx_to_tap(Cursor::new(*b"not-a-tzx-file!!"), &mut out).is_err());
+    }
+
+    #[test]
+    fn truncated_block_is_an_error_when_strict() {
+        // declares 4 bytes of payload but only 2 are actually present
+        let tap = [0x04, 0x00, 0xFF, 0x01];
+        let options = ConvertOptions {
+            validation: Validation::Strict,
+            ..Default::default()
+        };
+
+        let mut out = Vec::new();
+        assert!(convert(Cursor::new(tap), &mut out, options).is_err());
+    }
+
+    #[test]
+    fn strict_validation_escalates_default_checksum_policy_to_error() {
+        let data = [0xFFu8, 0x01, 0x02, 0x00]; // wrong checksum
+        let mut tap = Vec::new();
+        tap.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        tap.extend_from_slice(&data);
+
+        let options = ConvertOptions {
+            validation: Validation::Strict,
+            ..Default::default()
+        };
+
+        let mut out = Vec::new();
+        assert!(convert(Cursor::new(tap), &mut out, options).is_err());
+    }
+
+    #[test]
+    fn strict_validation_still_honours_explicit_ignore_checksum_policy() {
+        let data = [0xFFu8, 0x01, 0x02, 0x00]; // wrong checksum
+        let mut tap = Vec::new();
+        tap.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        tap.extend_from_slice(&data);
+
+        let options = ConvertOptions {
+            checksums: ChecksumPolicy::Ignore,
+            validation: Validation::Strict,
+            ..Default::default()
+        };
+
+        let mut out = Vec::new();
+        assert!(convert(Cursor::new(tap), &mut out, options).is_ok());
+    }
+
+    #[test]
+    fn truncated_block_is_salvaged_when_lenient() {
+        // declares 4 bytes of payload but only 2 are actually present
+        let tap = [0x04, 0x00, 0xFF, 0x01];
+        let options = ConvertOptions {
+            checksums: ChecksumPolicy::Ignore,
+            validation: Validation::Lenient,
+            ..Default::default()
+        };
+
+        let mut out = Vec::new();
+        let block_count = convert(Cursor::new(tap), &mut out, options).unwrap();
+
+        assert_eq!(1, block_count);
+        // file header (10) + block header (5) + the 2 bytes that were actually read
+        assert_eq!(10 + 5 + 2, out.len());
+        assert_eq!(&[0xFF, 0x01], &out[out.len() - 2..]);
+    }
+}
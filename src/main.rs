@@ -7,177 +7,145 @@ use std::{
     path::{Path, PathBuf},
 };
 
+use tap2tzx::{ChecksumPolicy, ConvertOptions, Metadata, Validation};
+
 fn main() -> anyhow::Result<()> {
-    let args: Vec<String> = env::args().collect();
+    let mut args: Vec<String> = env::args().skip(1).collect();
+
+    let strict = take_flag(&mut args, "--strict");
+    let metadata = take_flag(&mut args, "--metadata");
+    let checksums = match take_value(&mut args, "--checksums") {
+        Some(value) => parse_checksum_policy(&value)?,
+        None => ChecksumPolicy::default(),
+    };
 
     match args.len() {
-        2 => tap_to_tzx(&args[1], target(&args[1])),
-        3 => tap_to_tzx(&args[1], &args[2]),
+        1 => convert_file(&args[0], target(&args[0]), strict, metadata, checksums),
+        2 => convert_file(&args[0], &args[1], strict, metadata, checksums),
         _ => {
-            println!("\nUsage: tap2tzx INPUT.TAP [OUTPUT.TZX]");
+            println!(
+                "\nUsage: tap2tzx [--strict] [--metadata] [--checksums=ignore|warn|error] INPUT.TAP [OUTPUT.TZX]\n       tap2tzx INPUT.TZX [OUTPUT.TAP]"
+            );
             std::process::exit(0);
         }
     }
 }
 
-/// Takes a path to a .tap file and returns the equivalent .tzx path
-fn target<P>(tap_name: P) -> PathBuf
+/// Removes every occurrence of `flag` from `args`, returning whether it was present.
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    let present = args.iter().any(|arg| arg == flag);
+    args.retain(|arg| arg != flag);
+    present
+}
+
+/// Removes a `--name=value` argument from `args`, returning its value if present.
+fn take_value(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let prefix = format!("{}=", name);
+    let index = args.iter().position(|arg| arg.starts_with(&prefix))?;
+    Some(args.remove(index)[prefix.len()..].to_string())
+}
+
+/// Parses a `--checksums` value into the [`ChecksumPolicy`] it names.
+fn parse_checksum_policy(value: &str) -> anyhow::Result<ChecksumPolicy> {
+    match value {
+        "ignore" => Ok(ChecksumPolicy::Ignore),
+        "warn" => Ok(ChecksumPolicy::Warn),
+        "error" => Ok(ChecksumPolicy::Error),
+        other => Err(anyhow!(
+            "unknown --checksums value {:?} (expected ignore, warn or error)",
+            other
+        )),
+    }
+}
+
+/// Takes a path to a .tap or .tzx file and returns the path in the opposite format
+fn target<P>(in_name: P) -> PathBuf
 where
     P: AsRef<Path>,
 {
-    tap_name.as_ref().with_extension("tzx")
+    in_name
+        .as_ref()
+        .with_extension(if is_tzx(&in_name) { "tap" } else { "tzx" })
 }
 
-/// Takes a TAP path for an existing file and converts it to TZX format, writing the output to the TZX path
+/// Whether `path`'s extension is `.tzx` (case-insensitive)
+fn is_tzx<P>(path: P) -> bool
+where
+    P: AsRef<Path>,
+{
+    path.as_ref()
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("tzx"))
+}
+
+/// Converts between TAP and TZX, picking the direction from the input path's
+/// extension: a `.tzx` input is decoded back to TAP, anything else is assumed to be
+/// TAP and encoded to TZX.
 ///
-/// This will read the entire TAP file into memory, which should not be a problem since Spectrum files were so small
-/// and it is expected that this conversion programme will be used to create things for an emulator, and have much
-/// more resources available thatn a real Spectrum.
+/// Either path may be `-`, meaning read the input from stdin or write the output to
+/// stdout respectively. `strict` rejects a malformed TAP input outright instead of
+/// warning and converting what can be salvaged. `metadata` additionally emits a Text
+/// Description and Archive Info block for each TAP header block. `checksums`
+/// controls how a bad XOR checksum is handled.
 ///
 /// # Errors
 ///
-/// Can error if the TAP and TZX path are the same, or there are problems reading or writing the output.
-fn tap_to_tzx<I, O>(tap_path: I, tzx_path: O) -> anyhow::Result<()>
+/// Can error if there are problems reading the input or writing the output. If the input and
+/// output are the same file, prints a message and returns `Ok` without converting anything.
+fn convert_file<I, O>(
+    in_path: I,
+    out_path: O,
+    strict: bool,
+    metadata: bool,
+    checksums: ChecksumPolicy,
+) -> anyhow::Result<()>
 where
     I: AsRef<Path> + Debug,
     O: AsRef<Path> + Debug,
 {
-    if tzx_path.as_ref().exists()
-        && tap_path.as_ref().canonicalize()? == tzx_path.as_ref().canonicalize()?
+    if in_path.as_ref() != Path::new("-")
+        && out_path.as_ref() != Path::new("-")
+        && out_path.as_ref().exists()
+        && in_path.as_ref().canonicalize()? == out_path.as_ref().canonicalize()?
     {
-        println!("Not overwriting input file {:?}", tap_path);
+        eprintln!("Not overwriting input file {:?}", in_path);
         return Ok(());
     }
 
-    println!("Converting TAP {:?} to TZX at {:?}", tap_path, tzx_path);
-
-    // Open the tap as read-only
-    let mut fin = File::open(tap_path)?;
-
-    // Open the tzx file as write, create, truncate with inherited r+w user+group
-    let mut fout = BufWriter::new(File::create(tzx_path)?);
-
-    // The file will be small (Spectrum 128k, anyone?) so read it all into memory for easier manipulation
-    let mut tap: Vec<u8> = Vec::with_capacity(fin.metadata()?.len() as usize);
-    fin.read_to_end(&mut tap)?;
-
-    let block_count = tap2tzx(&tap, &mut fout)?;
-
-    println!("\nSuccesfully converted {} blocks!", block_count);
+    eprintln!("Converting {:?} to {:?}", in_path, out_path);
+
+    let input: Box<dyn Read> = if in_path.as_ref() == Path::new("-") {
+        Box::new(io::stdin())
+    } else {
+        Box::new(File::open(&in_path)?)
+    };
+
+    let mut output: Box<dyn Write> = if out_path.as_ref() == Path::new("-") {
+        Box::new(BufWriter::new(io::stdout()))
+    } else {
+        Box::new(BufWriter::new(File::create(out_path)?))
+    };
+
+    let block_count = if is_tzx(&in_path) {
+        tap2tzx::tzx_to_tap(input, &mut output)?
+    } else {
+        let options = ConvertOptions {
+            checksums,
+            metadata: Metadata {
+                text_description: metadata,
+                archive_info: metadata,
+            },
+            validation: if strict {
+                Validation::Strict
+            } else {
+                Validation::Lenient
+            },
+        };
+        tap2tzx::convert(input, &mut output, options)?
+    };
+
+    eprintln!("\nSuccesfully converted {} blocks!", block_count);
 
     Ok(())
 }
-
-/// Converts the provided TAP bytes by writing to TZX format in the provided out Write.
-///
-/// Returns the number of non-empty TZX blocks written to the output.
-///
-/// Callers should typically provide a BufWriter for more efficient syscall usage.
-fn tap2tzx<W>(tap: &[u8], tzx: &mut W) -> anyhow::Result<i32>
-where
-    W: Write,
-{
-    let size = tap.len() as usize;
-    write_tzx_header(tzx)?;
-
-    // loop through the tap file, reading each TAP block and writing TZX standard speed blocks to the output
-    let mut pos: usize = 0;
-    let mut block_count = 0;
-
-    while pos < size {
-        let block_len = read_le_u16(&mut &tap[pos as usize..], pos)?;
-
-        pos += 2;
-
-        if block_len != 0 {
-            write_tzx_block(tap, pos, block_len, tzx)?;
-        }
-
-        pos += block_len as usize;
-        block_count += 1;
-    }
-
-    tzx.flush()?;
-
-    Ok(block_count)
-}
-
-/// Write the tzx file header magic bytes
-fn write_tzx_header<W>(out: &mut W) -> io::Result<()>
-where
-    W: Write,
-{
-    // Magic start bytes plus version
-    out.write_all(&[b'Z', b'X', b'T', b'a', b'p', b'e', b'!', 0x1A, 1, 20])
-}
-
-/// Writes a full TZX block to the output
-fn write_tzx_block<W>(mem: &[u8], pos: usize, block_len: u16, out: &mut W) -> io::Result<()>
-where
-    W: Write,
-{
-    // Write the TZX block header
-
-    //  0
-    //  0 1 2 3 4
-    // +-+-+-+-+-+
-    // |I| P.| L.|
-    // +-+-+-+-+-+
-    //
-    // I - Block ID. 10u8 for Standard speed data block
-    // P - Pause after this block (ms.) {1000} (little endian}
-    // L - Length of data that follow (little endian)
-    //
-    out.write_all(&[0x10, 0xE8, 0x03])?; // I and P
-    out.write_all(&mem[pos - 2..pos])?; // length of data
-
-    // Write the TZX block data
-    out.write_all(&mem[pos..pos + block_len as usize])
-}
-
-/// Attempts to read a u16 from the provided slice.
-///
-/// Returns the u16 that was read if successful.
-fn read_le_u16(input: &mut &[u8], pos: usize) -> anyhow::Result<u16> {
-    // straight out of the language docs for how to read a u16 from a slice. See https://doc.rust-lang.org/std/primitive.u16.html#method.from_le_bytes
-    let mid = std::mem::size_of::<u16>();
-    if mid > input.len() {
-        return Err(anyhow!(
-            "Expected u16 but found u8 - malformed input at {}?",
-            pos
-        ));
-    }
-    let (int_bytes, rest) = input.split_at(mid);
-    *input = rest;
-    Ok(u16::from_le_bytes(int_bytes.try_into()?))
-}
-
-#[cfg(test)]
-mod test {
-
-    use crate::*;
-
-    #[test]
-    fn single_block() {
-        let tap = [
-            0x13, 0x00, 0x00, 0x00, 0x4D, 0x61, 0x6E, 0x69, 0x63, 0x4D, 0x69, 0x6E, 0x65, 0x72,
-            0x45, 0x00, 0x0A, 0x00, 0x45, 0x00, 0x1F,
-        ];
-        let mut out = Vec::with_capacity(10 + 3 + tap.len()); // file header + block header + data
-        let block_count = tap2tzx(&tap, &mut out).unwrap();
-
-        assert_eq!(1, block_count, "We expect to have created a single block");
-
-        // We expect to have the TZX file header, plus a single TZX block with header and data
-        let file_header = [b'Z', b'X', b'T', b'a', b'p', b'e', b'!', 0x1A, 1, 20];
-        let block_header = [0x10, 0xE8, 0x03]; // Standard speed data block with a pause of 1000ms
-        let expected: Vec<u8> = file_header
-            .iter()
-            .chain(block_header.iter())
-            .chain(tap.iter())
-            .map(|v| *v)
-            .collect();
-
-        assert_eq!(expected, out, "unexpected tzx byte stream");
-    }
-}